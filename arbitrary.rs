@@ -2,30 +2,148 @@
 
 
 use super::std;
-use super::std::hashmap::HashMap;
-use super::std::rand::{Rand, Rng, RngUtil};
+use super::std::hashmap::{HashMap, HashSet};
+use super::std::treemap::{TreeMap, TreeSet};
+use super::std::deque::RingBuf;
+use super::std::dlist::DList;
+use super::std::rand::{Rand, Rng, RngUtil, SeedableRng};
 
 /* Arbitrary */
 
+/**
+ A Gen is an Rng plus a notion of the "size" that generated values should be
+ scaled to (the size of vectors, the magnitude of numbers, and so on). All
+ entropy used by `arbitrary` flows through a Gen, so a fixed seed always
+ reproduces the same run.
+ */
+pub trait Gen: Rng {
+    fn size(&self) -> uint;
+}
+
+/// The standard Gen: any Rng, paired with a size.
+pub struct StdGen<R> {
+    priv rng: R,
+    priv size: uint,
+}
+
+impl<R: Rng> StdGen<R> {
+    pub fn new(rng: R, size: uint) -> StdGen<R> {
+        StdGen { rng: rng, size: size }
+    }
+}
+
+impl<R: Rng> Rng for StdGen<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+}
+
+impl<R: Rng> Gen for StdGen<R> {
+    fn size(&self) -> uint {
+        self.size
+    }
+}
+
+/**
+ A Gen that forwards all randomness to another Gen but reports a different
+ `size()`. Used to shrink the size budget as generation recurses into
+ nested structures, so e.g. a `#[deriving(Arbitrary)]` recursive enum
+ eventually bottoms out instead of generating forever.
+ */
+pub struct SizedGen<'a, G> {
+    priv gen: &'a mut G,
+    priv size: uint,
+}
+
+impl<'a, G> SizedGen<'a, G> {
+    pub fn new(gen: &'a mut G, size: uint) -> SizedGen<'a, G> {
+        SizedGen { gen: gen, size: size }
+    }
+}
+
+impl<'a, G: Rng> Rng for SizedGen<'a, G> {
+    fn next_u32(&mut self) -> u32 {
+        self.gen.next_u32()
+    }
+}
+
+impl<'a, G: Rng> Gen for SizedGen<'a, G> {
+    fn size(&self) -> uint {
+        self.size
+    }
+}
+
 /**
  The Arbitrary trait can generate a randomly chosen value (with restrictions).
- You can pass a size factor to allow specifying test size (sizes of vectors and
- numbers).
+ All entropy comes from the Gen passed in, so the same Gen (the same seed)
+ always yields the same value.
  */
 #[allow(default_methods)]
 pub trait Arbitrary {
     /**
-     arbitrary should return an arbitrary value of its type.
-     The value should be randomly chosen and its size should be scaled by the size
-     parameter.
+     arbitrary should return an arbitrary value of its type, drawing its
+     randomness from `g` and scaling its size (of vectors, numbers, etc) by
+     `g.size()`.
+     */
+    fn arbitrary<G: Gen>(g: &mut G) -> Self;
+
+    /**
+     shrink returns a set of values "smaller" than self, structurally derived
+     from it, to be tried in place of self when self causes a property to
+     fail. The default implementation returns no candidates, which is correct
+     (if unhelpful) for types with no obvious notion of a smaller value.
      */
-    fn arbitrary(uint) -> Self;
+    fn shrink(&self) -> ~[Self] {
+        ~[]
+    }
 }
 
 /// Create an arbitrary value of type T
 #[inline]
-pub fn arbitrary<T: Arbitrary>(sz: uint) -> T {
-    Arbitrary::arbitrary(sz)
+pub fn arbitrary<T: Arbitrary, G: Gen>(g: &mut G) -> T {
+    Arbitrary::arbitrary(g)
+}
+
+/**
+ Repeatedly replace a failing witness with the first of its shrink
+ candidates that still fails `prop`, until none of the candidates fail.
+ Returns the smallest witness found this way.
+ */
+pub fn shrink_failure<T: Arbitrary>(prop: |&T| -> bool, witness: T) -> T {
+    let mut smallest = witness;
+    loop {
+        match smallest.shrink().move_iter().find(|candidate| !prop(candidate)) {
+            Some(candidate) => smallest = candidate,
+            None => return smallest,
+        }
+    }
+}
+
+/**
+ Build a seeded StdGen: an explicit `seed` replays a previously reported
+ failure exactly, while `None` draws a fresh seed from the OS and prints it
+ so the run can be replayed later.
+ */
+pub fn seeded_gen(seed: Option<uint>, size: uint) -> StdGen<std::rand::StdRng> {
+    let seed = seed.unwrap_or_else(|| std::rand::random());
+    println!("qc.rs: using seed {} (pass it back in to replay this run)", seed);
+    let rng: std::rand::StdRng = SeedableRng::from_seed(&[seed]);
+    StdGen::new(rng, size)
+}
+
+/**
+ Run `prop` against up to `tests` arbitrary values drawn from `g`. On the
+ first failure, shrinks it to a minimal witness and returns it; returns
+ `None` if every case passed.
+ */
+pub fn quickcheck<T: Arbitrary, G: Gen>(prop: |&T| -> bool, g: &mut G, tests: uint) -> Option<T> {
+    for _ in range(0, tests) {
+        let witness: T = arbitrary(g);
+        if !prop(&witness) {
+            return Some(shrink_failure(prop, witness));
+        }
+    }
+    None
 }
 
 /// A wrapper type to reuse an existing Rand instance for the Arbitrary impl
@@ -43,41 +161,154 @@ pub struct NonEmptyVec<T>(~[T]);
 #[deriving(Eq, Clone)]
 pub struct SmallN(uint);
 
-fn small_n(size: uint) -> uint {
-    let f: std::rand::distributions::Exp1 = std::rand::random();
-    let n = (*f) * (size as f64) as uint;
-    n.min(&(16 * size))
+/// A wrapper around a list of `(weight, value)` pairs, so a value that
+/// should be rare (e.g. an error case) can be drawn with lower probability
+/// than the others. Sampling is O(1) after an O(n) setup, via Vose's alias
+/// method.
+#[deriving(Clone)]
+pub struct Frequency<T> {
+    priv choices: ~[T],
+    priv alias: Alias,
 }
 
-fn gen_unicode_str<R: Rng>(rng: &mut R, len: uint) -> ~str {
+impl<T: Clone> Frequency<T> {
+    pub fn new(weighted: ~[(uint, T)]) -> Frequency<T> {
+        let weights: ~[f64] = weighted.iter().map(|&(w, _)| w as f64).collect();
+        let choices: ~[T] = weighted.move_iter().map(|(_, v)| v).collect();
+        Frequency { choices: choices, alias: Alias::new(weights) }
+    }
+
+    /// Draw one of the wrapped values, biased by its weight.
+    pub fn sample<G: Gen>(&self, g: &mut G) -> T {
+        self.choices[self.alias.sample(g)].clone()
+    }
+}
+
+/**
+ Vose's alias method: given a set of weights, sample an index in O(1) after
+ an O(n) setup. Each outcome `i` either keeps its own probability `prob[i]`
+ or, with the remaining probability, defers to `alias[i]`.
+ */
+#[deriving(Clone)]
+pub struct Alias {
+    priv prob: ~[f64],
+    priv alias: ~[uint],
+}
+
+impl Alias {
+    /// Build a sampler over `weights.len()` outcomes. Weights must be
+    /// non-negative and not all zero.
+    pub fn new(weights: ~[f64]) -> Alias {
+        let n = weights.len();
+        let sum: f64 = weights.iter().fold(0.0, |a, &b| a + b);
+        let mut p: ~[f64] = weights.iter().map(|&w| w * (n as f64) / sum).collect();
+
+        let mut prob = std::vec::from_elem(n, 0.0f64);
+        let mut alias = std::vec::from_elem(n, 0u);
+
+        let mut small: ~[uint] = range(0, n).filter(|&i| p[i] < 1.0).collect();
+        let mut large: ~[uint] = range(0, n).filter(|&i| p[i] >= 1.0).collect();
+
+        while !small.is_empty() && !large.is_empty() {
+            let s = small.pop().unwrap();
+            let l = large.pop().unwrap();
+            prob[s] = p[s];
+            alias[s] = l;
+            p[l] = p[l] - (1.0 - p[s]);
+            if p[l] < 1.0 { small.push(l); } else { large.push(l); }
+        }
+        // Leftover indices (rounding error only) keep their own outcome.
+        for &i in large.iter() { prob[i] = 1.0; }
+        for &i in small.iter() { prob[i] = 1.0; }
+
+        Alias { prob: prob, alias: alias }
+    }
+
+    /// Draw one outcome index in O(1).
+    pub fn sample<G: Gen>(&self, g: &mut G) -> uint {
+        let i = g.gen_range(0u, self.prob.len());
+        let u: f64 = g.gen();
+        if u < self.prob[i] { i } else { self.alias[i] }
+    }
+}
+
+/// Shrink an unsigned magnitude towards 0: 0, self/2, self - self/2, and so
+/// on, halving the remaining distance to 0 each step.
+fn shrink_towards_zero(x: uint) -> ~[uint] {
+    let mut result = ~[];
+    if x == 0 { return result; }
+    result.push(0);
+    let mut n = x;
+    loop {
+        let half = n / 2;
+        if half == 0 { break; }
+        let other = n - half;
+        result.push(half);
+        if other != half { result.push(other); }
+        n = half;
+    }
+    result
+}
+
+/// Shrink a signed value towards 0, the same way as `shrink_towards_zero`
+/// plus the negation (of either sign, per "for signed also the
+/// negation"). Computes the magnitude without ever negating (or calling
+/// `abs` on) `Self::min_value()` directly, since that overflows back to
+/// the same (negative) value in two's complement and would make
+/// `shrink()` re-emit `self` as its own "smaller" candidate; the same
+/// overflow check also guards the negated candidate itself.
+macro_rules! shrink_signed( ($T:ty) => (
+        fn shrink(&self) -> ~[$T] {
+            let mut result = ~[];
+            let neg = -*self;
+            if neg != *self { result.push(neg); }
+            let mag = if *self >= 0 {
+                *self as uint
+            } else {
+                (-(*self + 1)) as uint + 1
+            };
+            for n in shrink_towards_zero(mag).iter() {
+                result.push(*n as $T);
+            }
+            result
+        }
+    )
+)
+
+fn small_n<G: Gen>(g: &mut G) -> uint {
+    let f: std::rand::distributions::Exp1 = g.gen();
+    let n = (*f) * (g.size() as f64) as uint;
+    n.min(&(16 * g.size()))
+}
+
+fn gen_unicode_str<G: Gen>(g: &mut G, len: uint) -> ~str {
     let text = ~"\
 a b c 0 $ ⇌ [ˈʏpsilɔn] \\ \" ‚dsch‘ „füh“      ‡ € ⁿ ２ � 🈘
-ἀπὸ состоится ทรงนับถือขันทีเป็นที่พึ่ง Hello world Καλημέρα κόσμε コンニチハ";
+ἀπὸ состоится ทรงนับถือขันทีเป็นที่พึ่ง Hello world Καλημέρα κόσμε コンニチハ";
     let mut res = ~"";
     let mut words: ~[&str] = text.word_iter().collect();
     words.push_all([" ", " ", "\n"]);
     while res.len() < len {
-        res += rng.choose(words);
+        res += g.choose(words);
     }
     res
 }
 
 /* Helper: Iter */
-#[deriving(Clone)]
-priv struct Iter<T> {
+priv struct Iter<'a, G, T> {
     count: uint,
-    size: uint,
+    gen: &'a mut G,
 }
 
-fn arbiter<T: Arbitrary>(count: uint, sz: uint) -> Iter<T> {
-    Iter{count: count, size: sz }
+fn arbiter<'a, G: Gen, T: Arbitrary>(count: uint, g: &'a mut G) -> Iter<'a, G, T> {
+    Iter { count: count, gen: g }
 }
 
-impl<T: Arbitrary> Iterator<T> for Iter<T> {
+impl<'a, G: Gen, T: Arbitrary> Iterator<T> for Iter<'a, G, T> {
     fn next(&mut self) -> Option<T> {
         if self.count > 0 {
             self.count -= 1;
-            Some(arbitrary(self.size))
+            Some(arbitrary(self.gen))
         } else { None }
     }
 
@@ -89,8 +320,8 @@ impl<T: Arbitrary> Iterator<T> for Iter<T> {
 
 macro_rules! arb_rand( ($T:ty) => (
         impl Arbitrary for $T {
-            fn arbitrary(_: uint) -> $T {
-                std::rand::random()
+            fn arbitrary<G: Gen>(g: &mut G) -> $T {
+                g.gen()
             }
         }
     )
@@ -98,17 +329,24 @@ macro_rules! arb_rand( ($T:ty) => (
 
 macro_rules! arb_tuple( ($($T:ident),+ -> $($S:expr),+) => (
         impl<$($T: Clone + Arbitrary),+> Arbitrary for ($($T),+) {
-            fn arbitrary(sz: uint) -> ($($T),+) {
-                ($(Arbitrary::arbitrary::<$T>(sz)),+)
+            fn arbitrary<G: Gen>(g: &mut G) -> ($($T),+) {
+                ($(Arbitrary::arbitrary::<$T, G>(g)),+)
+            }
+
+            fn shrink(&self) -> ~[($($T),+)] {
+                let ($(ref $T),+) = *self;
+                let mut result = ~[];
+                $(
+                    for s in $T.shrink().move_iter() {
+                        result.push($S);
+                    }
+                )+
+                result
             }
         }
     )
 )
 
-arb_rand!(i8)
-//arb_rand!(u8)
-arb_rand!(int)
-arb_rand!(uint)
 arb_rand!(float)
 arb_rand!(bool)
 arb_rand!(char)
@@ -141,53 +379,134 @@ arb_tuple!(A, B, C, D, E, F ->
     (A.clone(), B.clone(), C.clone(), D.clone(), E.clone(), s))
 
 impl<T: Rand> Arbitrary for Random<T> {
-    fn arbitrary(_: uint) -> Random<T> {
-        Random(std::rand::random())
+    fn arbitrary<G: Gen>(g: &mut G) -> Random<T> {
+        Random(g.gen())
     }
 }
 
 impl<T: Arbitrary> Arbitrary for ~T {
     #[inline]
-    fn arbitrary(sz: uint) -> ~T {
-        ~arbitrary(sz)
+    fn arbitrary<G: Gen>(g: &mut G) -> ~T {
+        ~arbitrary(g)
     }
 }
 
 impl Arbitrary for u8 {
-    fn arbitrary(_: uint) -> u8 {
-        std::rand::random()
+    fn arbitrary<G: Gen>(g: &mut G) -> u8 {
+        g.gen()
+    }
+
+    fn shrink(&self) -> ~[u8] {
+        shrink_towards_zero(*self as uint).map(|&n| n as u8)
+    }
+}
+
+impl Arbitrary for i8 {
+    fn arbitrary<G: Gen>(g: &mut G) -> i8 {
+        g.gen()
+    }
+
+    shrink_signed!(i8)
+}
+
+impl Arbitrary for int {
+    fn arbitrary<G: Gen>(g: &mut G) -> int {
+        g.gen()
+    }
+
+    shrink_signed!(int)
+}
+
+impl Arbitrary for uint {
+    fn arbitrary<G: Gen>(g: &mut G) -> uint {
+        g.gen()
+    }
+
+    fn shrink(&self) -> ~[uint] {
+        shrink_towards_zero(*self)
     }
 }
 
 impl Arbitrary for SmallN {
-    fn arbitrary(sz: uint) -> SmallN {
-        SmallN(small_n(sz))
+    fn arbitrary<G: Gen>(g: &mut G) -> SmallN {
+        SmallN(small_n(g))
     }
 }
 
 impl<T: Clone + Arbitrary> Arbitrary for ~[T] {
-    fn arbitrary(sz: uint) -> ~[T] {
-        arbiter::<T>(small_n(sz), sz).collect()
+    fn arbitrary<G: Gen>(g: &mut G) -> ~[T] {
+        let n = small_n(g);
+        arbiter(n, g).collect()
+    }
+
+    fn shrink(&self) -> ~[~[T]] {
+        let mut result = ~[];
+        let n = self.len();
+        if n == 0 { return result; }
+
+        // Each single element removed.
+        for i in range(0, n) {
+            result.push(self.iter().enumerate()
+                        .filter_map(|(j, x)| if i == j { None } else { Some(x.clone()) })
+                        .collect());
+        }
+
+        // The two halves.
+        if n > 1 {
+            let mid = n / 2;
+            result.push(self.slice(0, mid).to_owned());
+            result.push(self.slice(mid, n).to_owned());
+        }
+
+        // Each element replaced by each of its own shrinks.
+        for i in range(0, n) {
+            for s in self[i].shrink().move_iter() {
+                let mut v = self.to_owned();
+                v[i] = s;
+                result.push(v);
+            }
+        }
+
+        result
     }
 }
 
 impl<T: Arbitrary> Arbitrary for Option<T> {
-    fn arbitrary(sz: uint) -> Option<T> {
-        if std::rand::random() {
-            Some(arbitrary(sz))
+    fn arbitrary<G: Gen>(g: &mut G) -> Option<T> {
+        if g.gen() {
+            Some(arbitrary(g))
         } else {
             None
         }
     }
 
+    fn shrink(&self) -> ~[Option<T>] {
+        match *self {
+            None => ~[],
+            Some(ref x) => {
+                let mut result = ~[None];
+                for s in x.shrink().move_iter() {
+                    result.push(Some(s));
+                }
+                result
+            }
+        }
+    }
 }
 
 impl<T: Arbitrary, U: Arbitrary> Arbitrary for Result<T, U> {
-    fn arbitrary(sz: uint) -> Result<T, U> {
-        if std::rand::random() {
-            Ok(arbitrary(sz))
+    fn arbitrary<G: Gen>(g: &mut G) -> Result<T, U> {
+        if g.gen() {
+            Ok(arbitrary(g))
         } else {
-            Err(arbitrary(sz))
+            Err(arbitrary(g))
+        }
+    }
+
+    fn shrink(&self) -> ~[Result<T, U>] {
+        match *self {
+            Ok(ref x) => x.shrink().move_iter().map(Ok).collect(),
+            Err(ref x) => x.shrink().move_iter().map(Err).collect(),
         }
     }
 }
@@ -195,36 +514,346 @@ impl<T: Arbitrary, U: Arbitrary> Arbitrary for Result<T, U> {
 
 
 impl<T: Clone + Arbitrary> Arbitrary for NonEmptyVec<T> {
-    fn arbitrary(sz: uint) -> NonEmptyVec<T> {
-        let n = 1 + small_n(sz);
-        NonEmptyVec(arbiter::<T>(n, sz).collect())
+    fn arbitrary<G: Gen>(g: &mut G) -> NonEmptyVec<T> {
+        let n = 1 + small_n(g);
+        NonEmptyVec(arbiter(n, g).collect())
+    }
+
+    fn shrink(&self) -> ~[NonEmptyVec<T>] {
+        let NonEmptyVec(ref v) = *self;
+        v.shrink().move_iter()
+            .filter(|s| !s.is_empty())
+            .map(NonEmptyVec)
+            .collect()
+    }
+}
+
+/// Shrink a string as if it were a vector of chars: elements removed, the
+/// two halves, but no per-char shrinking (chars have no smaller chars).
+fn shrink_str_like_chars(s: &str) -> ~[~str] {
+    let chars: ~[char] = s.chars().collect();
+    let n = chars.len();
+    let mut result = ~[];
+    if n == 0 { return result; }
+
+    for i in range(0, n) {
+        let v: ~[char] = chars.iter().enumerate()
+            .filter_map(|(j, c)| if i == j { None } else { Some(*c) })
+            .collect();
+        result.push(std::str::from_chars(v));
     }
 
+    if n > 1 {
+        let mid = n / 2;
+        result.push(std::str::from_chars(chars.slice(0, mid)));
+        result.push(std::str::from_chars(chars.slice(mid, n)));
+    }
+
+    result
 }
 
 impl Arbitrary for ~str {
-    fn arbitrary(sz: uint) -> ~str {
-        let rng = &mut *std::rand::task_rng();
-        let n = small_n(sz);
-        rng.gen_str(n)
+    fn arbitrary<G: Gen>(g: &mut G) -> ~str {
+        let n = small_n(g);
+        g.gen_str(n)
+    }
+
+    fn shrink(&self) -> ~[~str] {
+        shrink_str_like_chars(*self)
     }
 }
 
 impl Arbitrary for Unicode {
-    fn arbitrary(sz: uint) -> Unicode {
-        let rng = &mut *std::rand::task_rng();
-        let n = small_n(sz);
-        Unicode(gen_unicode_str(rng, n))
+    fn arbitrary<G: Gen>(g: &mut G) -> Unicode {
+        let n = small_n(g);
+        Unicode(gen_unicode_str(g, n))
+    }
+
+    fn shrink(&self) -> ~[Unicode] {
+        let Unicode(ref s) = *self;
+        shrink_str_like_chars(*s).move_iter().map(Unicode).collect()
     }
 }
 
 impl<K: Arbitrary + Eq + Hash, V: Arbitrary> Arbitrary for HashMap<K, V> {
-    fn arbitrary(sz: uint) -> HashMap<K, V> {
-        let n: uint = small_n(sz);
+    fn arbitrary<G: Gen>(g: &mut G) -> HashMap<K, V> {
+        let n: uint = small_n(g);
         let mut v = HashMap::new();
-        for n.times {
-            v.insert(arbitrary(sz), arbitrary(sz));
+        for _ in range(0, n) {
+            v.insert(arbitrary(g), arbitrary(g));
+        }
+        v
+    }
+}
+
+impl<T: Arbitrary + Eq + Hash> Arbitrary for HashSet<T> {
+    fn arbitrary<G: Gen>(g: &mut G) -> HashSet<T> {
+        let n: uint = small_n(g);
+        let mut v = HashSet::new();
+        for _ in range(0, n) {
+            v.insert(arbitrary(g));
         }
         v
     }
 }
+
+impl<K: Arbitrary + TotalOrd, V: Arbitrary> Arbitrary for TreeMap<K, V> {
+    fn arbitrary<G: Gen>(g: &mut G) -> TreeMap<K, V> {
+        let n: uint = small_n(g);
+        let mut v = TreeMap::new();
+        for _ in range(0, n) {
+            v.insert(arbitrary(g), arbitrary(g));
+        }
+        v
+    }
+}
+
+impl<T: Arbitrary + TotalOrd> Arbitrary for TreeSet<T> {
+    fn arbitrary<G: Gen>(g: &mut G) -> TreeSet<T> {
+        let n: uint = small_n(g);
+        let mut v = TreeSet::new();
+        for _ in range(0, n) {
+            v.insert(arbitrary(g));
+        }
+        v
+    }
+}
+
+/// A ring buffer / deque.
+impl<T: Arbitrary> Arbitrary for RingBuf<T> {
+    fn arbitrary<G: Gen>(g: &mut G) -> RingBuf<T> {
+        let n: uint = small_n(g);
+        let mut v = RingBuf::new();
+        for _ in range(0, n) {
+            v.push_back(arbitrary(g));
+        }
+        v
+    }
+}
+
+/// A doubly-linked list.
+impl<T: Arbitrary> Arbitrary for DList<T> {
+    fn arbitrary<G: Gen>(g: &mut G) -> DList<T> {
+        let n: uint = small_n(g);
+        let mut v = DList::new();
+        for _ in range(0, n) {
+            v.push_back(arbitrary(g));
+        }
+        v
+    }
+}
+
+/// An integer constrained to the half-open range `[lo, hi)`, mirroring
+/// `Rng::gen_range` rather than the full `int` range `Arbitrary` otherwise
+/// generates.
+#[deriving(Eq, Clone)]
+pub struct Ranged(int, int);
+
+impl Ranged {
+    pub fn sample<G: Gen>(&self, g: &mut G) -> int {
+        let Ranged(lo, hi) = *self;
+        g.gen_range(lo, hi)
+    }
+}
+
+/// A float constrained to the half-open range `[lo, hi)`.
+#[deriving(Clone)]
+pub struct RangedFloat(f64, f64);
+
+impl RangedFloat {
+    pub fn sample<G: Gen>(&self, g: &mut G) -> f64 {
+        let RangedFloat(lo, hi) = *self;
+        g.gen_range(lo, hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shrink_towards_zero, Arbitrary, Alias, Frequency, StdGen};
+    use super::{arbitrary, seeded_gen, quickcheck, Ranged, RangedFloat};
+    use super::std;
+    use super::std::hashmap::HashSet;
+    use super::std::treemap::{TreeMap, TreeSet};
+    use super::std::deque::RingBuf;
+    use super::std::dlist::DList;
+
+    #[test]
+    fn shrink_towards_zero_of_zero_is_empty() {
+        assert_eq!(shrink_towards_zero(0), ~[]);
+    }
+
+    #[test]
+    fn shrink_towards_zero_of_one() {
+        assert_eq!(shrink_towards_zero(1), ~[0]);
+    }
+
+    #[test]
+    fn shrink_towards_zero_contains_zero_and_only_smaller_values() {
+        let s = shrink_towards_zero(37);
+        assert!(s.contains(&0));
+        assert!(s.iter().all(|&n| n < 37));
+    }
+
+    #[test]
+    fn i8_shrink_of_min_value_does_not_reemit_self() {
+        let min: i8 = std::i8::min_value();
+        let s = min.shrink();
+        assert!(!s.is_empty());
+        assert!(!s.iter().any(|&n| n == min));
+    }
+
+    #[test]
+    fn int_shrink_of_min_value_does_not_reemit_self() {
+        let min: int = std::int::min_value();
+        let s = min.shrink();
+        assert!(!s.is_empty());
+        assert!(!s.iter().any(|&n| n == min));
+    }
+
+    #[test]
+    fn int_shrink_of_positive_includes_negation() {
+        let s = 5i.shrink();
+        assert!(s.contains(&-5));
+    }
+
+    fn test_gen() -> StdGen<std::rand::StdRng> {
+        let rng: std::rand::StdRng = std::rand::SeedableRng::from_seed(&[42u]);
+        StdGen::new(rng, 10)
+    }
+
+    #[test]
+    fn alias_samples_every_index_with_uniform_weights() {
+        let alias = Alias::new(~[1.0, 1.0, 1.0, 1.0]);
+        let mut g = test_gen();
+        let mut counts = [0u, 0, 0, 0];
+        for _ in range(0, 4000) {
+            counts[alias.sample(&mut g)] += 1;
+        }
+        // With 1000 expected draws per bucket, a uniform sampler should
+        // land comfortably within +/- 250 of that; a broken partition
+        // step would skew far more than this.
+        for &c in counts.iter() {
+            assert!(c > 750 && c < 1250, "count {} out of range", c);
+        }
+    }
+
+    #[test]
+    fn alias_respects_relative_weights() {
+        // Index 0 is 9x as likely as index 1.
+        let alias = Alias::new(~[9.0, 1.0]);
+        let mut g = test_gen();
+        let mut counts = [0u, 0];
+        for _ in range(0, 4000) {
+            counts[alias.sample(&mut g)] += 1;
+        }
+        assert!(counts[0] > counts[1] * 3);
+    }
+
+    #[test]
+    fn frequency_samples_only_its_given_values() {
+        let freq = Frequency::new(~[(1u, "rare"), (9u, "common")]);
+        let mut g = test_gen();
+        for _ in range(0, 100) {
+            let v = freq.sample(&mut g);
+            assert!(v == "rare" || v == "common");
+        }
+    }
+
+    #[test]
+    fn seeded_gen_with_same_seed_reproduces_the_same_sequence() {
+        let mut g1 = seeded_gen(Some(123), 10);
+        let mut g2 = seeded_gen(Some(123), 10);
+        let xs1: ~[uint] = range(0, 20).map(|_| arbitrary(&mut g1)).collect();
+        let xs2: ~[uint] = range(0, 20).map(|_| arbitrary(&mut g2)).collect();
+        assert_eq!(xs1, xs2);
+    }
+
+    #[test]
+    fn seeded_gen_with_different_seeds_usually_diverges() {
+        let mut g1 = seeded_gen(Some(123), 10);
+        let mut g2 = seeded_gen(Some(456), 10);
+        let xs1: ~[uint] = range(0, 20).map(|_| arbitrary(&mut g1)).collect();
+        let xs2: ~[uint] = range(0, 20).map(|_| arbitrary(&mut g2)).collect();
+        assert!(xs1 != xs2);
+    }
+
+    #[test]
+    fn quickcheck_with_same_seed_replays_the_same_failing_witness() {
+        let mut g1 = seeded_gen(Some(7), 20);
+        let mut g2 = seeded_gen(Some(7), 20);
+        let w1 = quickcheck(|n: &uint| *n < 1000000, &mut g1, 200);
+        let w2 = quickcheck(|n: &uint| *n < 1000000, &mut g2, 200);
+        assert_eq!(w1, w2);
+    }
+
+    #[test]
+    fn ranged_stays_within_bounds() {
+        let r = Ranged(-5, 5);
+        let mut g = test_gen();
+        for _ in range(0, 500) {
+            let n = r.sample(&mut g);
+            assert!(n >= -5 && n < 5);
+        }
+    }
+
+    #[test]
+    fn ranged_float_stays_within_bounds() {
+        let r = RangedFloat(-1.0, 1.0);
+        let mut g = test_gen();
+        for _ in range(0, 500) {
+            let n = r.sample(&mut g);
+            assert!(n >= -1.0 && n < 1.0);
+        }
+    }
+
+    // `small_n` caps a collection's length at `16 * size`; every bounded
+    // collection impl should respect that, the same way `~[T]` already does.
+    fn assert_bounded_len(len: uint, size: uint) {
+        assert!(len <= 16 * size, "length {} exceeds 16 * size ({})", len, 16 * size);
+    }
+
+    #[test]
+    fn hash_set_len_is_bounded_by_size() {
+        let mut g = test_gen();
+        for _ in range(0, 20) {
+            let s: HashSet<uint> = arbitrary(&mut g);
+            assert_bounded_len(s.len(), g.size());
+        }
+    }
+
+    #[test]
+    fn tree_map_len_is_bounded_by_size() {
+        let mut g = test_gen();
+        for _ in range(0, 20) {
+            let m: TreeMap<uint, uint> = arbitrary(&mut g);
+            assert_bounded_len(m.len(), g.size());
+        }
+    }
+
+    #[test]
+    fn tree_set_len_is_bounded_by_size() {
+        let mut g = test_gen();
+        for _ in range(0, 20) {
+            let s: TreeSet<uint> = arbitrary(&mut g);
+            assert_bounded_len(s.len(), g.size());
+        }
+    }
+
+    #[test]
+    fn ring_buf_len_is_bounded_by_size() {
+        let mut g = test_gen();
+        for _ in range(0, 20) {
+            let d: RingBuf<uint> = arbitrary(&mut g);
+            assert_bounded_len(d.len(), g.size());
+        }
+    }
+
+    #[test]
+    fn dlist_len_is_bounded_by_size() {
+        let mut g = test_gen();
+        for _ in range(0, 20) {
+            let l: DList<uint> = arbitrary(&mut g);
+            assert_bounded_len(l.len(), g.size());
+        }
+    }
+}
@@ -0,0 +1,194 @@
+// vim: sts=4 sw=4 et
+
+//! `#[deriving(Arbitrary)]`, registered from the crate root via
+//! `#[plugin_registrar]` alongside the other `deriving` extensions:
+//!
+//!     reg.register_syntax_extension(intern("Arbitrary"),
+//!                                    ItemDecorator(expand_deriving_arbitrary));
+
+use syntax::ast;
+use syntax::ast::{Item, MetaItem, StructDef, EnumDef, Ident};
+use syntax::codemap::Span;
+use syntax::ext::base::ExtCtxt;
+use syntax::ext::build::AstBuilder;
+use syntax::ext::quote::rt::ToSource;
+
+/// Does this field type mention `name`, i.e. is this field (perhaps nested
+/// in a box or vector) possibly a recursive occurrence of the type being
+/// derived for? A shallow, textual check is enough to keep generation from
+/// diverging; it doesn't need to be exact.
+fn mentions(ty: &ast::Ty, name: Ident) -> bool {
+    ty.to_source().contains(name.to_source())
+}
+
+/// A variant's relative weight, from `#[weight = N]`, defaulting to 1 so an
+/// undecorated enum is chosen from uniformly.
+fn variant_weight(variant: &ast::Variant) -> uint {
+    for attr in variant.node.attrs.iter() {
+        match attr.node.value.node {
+            ast::MetaNameValue(ref name, ref lit) if name.get() == "weight" => {
+                match lit.node {
+                    ast::LitInt(n, _) | ast::LitUint(n, _) => return n as uint,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    1
+}
+
+fn expr_weight_vec(cx: &mut ExtCtxt, span: Span, weights: &[uint]) -> @ast::Expr {
+    let exprs: ~[@ast::Expr] = weights.iter().map(|&w| quote_expr!(cx, $w as f64)).collect();
+    // `Alias::new` takes an owned `~[f64]`, not a fixed-size `[f64, ..N]`,
+    // so this must build a unique vec literal, not a stack array.
+    cx.expr_vec_uniq(span, exprs)
+}
+
+/// `arbitrary(g)` for a single field. A recursive field gets `g`'s size
+/// halved, so that recursive structures shrink towards a base case as
+/// generation recurses; a non-recursive field uses `g` as it is.
+fn arbitrary_field(cx: &mut ExtCtxt, recursive: bool) -> @ast::Expr {
+    if recursive {
+        quote_expr!(cx, ::qc::arbitrary::arbitrary(
+            &mut ::qc::arbitrary::SizedGen::new(g, g.size() / 2)))
+    } else {
+        quote_expr!(cx, ::qc::arbitrary::arbitrary(g))
+    }
+}
+
+fn expand_struct(cx: &mut ExtCtxt, span: Span, name: Ident, def: &StructDef) -> @ast::Item {
+    let fields: ~[@ast::Expr] = def.fields.iter().map(|f| {
+        let recursive = mentions(f.node.ty, name);
+        let value = arbitrary_field(cx, recursive);
+        match f.node.kind {
+            ast::NamedField(ident, _) => quote_expr!(cx, $ident: $value),
+            ast::UnnamedField(_) => value,
+        }
+    }).collect();
+
+    let body = if def.fields.iter().any(|f| match f.node.kind {
+        ast::NamedField(..) => true,
+        _ => false,
+    }) {
+        cx.expr_struct_ident(span, name, fields)
+    } else {
+        cx.expr_call_ident(span, name, fields)
+    };
+
+    quote_item!(cx,
+        impl ::qc::arbitrary::Arbitrary for $name {
+            fn arbitrary<G: ::qc::arbitrary::Gen>(g: &mut G) -> $name {
+                $body
+            }
+        }
+    ).unwrap()
+}
+
+fn expand_enum(cx: &mut ExtCtxt, span: Span, name: Ident, def: &EnumDef) -> @ast::Item {
+    let n = def.variants.len();
+    let arms: ~[ast::Arm] = def.variants.iter().enumerate().map(|(i, variant)| {
+        let vname = variant.node.name;
+        let recursive = match variant.node.kind {
+            ast::TupleVariantKind(ref args) =>
+                args.iter().any(|a| mentions(a.ty, name)),
+            ast::StructVariantKind(ref sd) =>
+                sd.fields.iter().any(|f| mentions(f.node.ty, name)),
+        };
+        let ctor = match variant.node.kind {
+            ast::TupleVariantKind(ref args) => {
+                let fields: ~[@ast::Expr] = args.iter()
+                    .map(|_| arbitrary_field(cx, recursive)).collect();
+                if fields.is_empty() {
+                    cx.expr_ident(span, vname)
+                } else {
+                    cx.expr_call_ident(span, vname, fields)
+                }
+            }
+            ast::StructVariantKind(ref sd) => {
+                let fields: ~[@ast::Expr] = sd.fields.iter()
+                    .map(|_| arbitrary_field(cx, recursive)).collect();
+                cx.expr_struct_ident(span, vname, fields)
+            }
+        };
+        cx.arm(span, ~[cx.pat_lit(span, cx.expr_uint(span, i))], ctor)
+    }).collect();
+
+    // As the size budget shrinks to zero, only consider variants that don't
+    // recurse into this same type, so generation is guaranteed to bottom out.
+    let non_recursive: ~[uint] = def.variants.iter().enumerate()
+        .filter(|&(_, variant)| match variant.node.kind {
+            ast::TupleVariantKind(ref args) => !args.iter().any(|a| mentions(a.ty, name)),
+            ast::StructVariantKind(ref sd) => !sd.fields.iter().any(|f| mentions(f.node.ty, name)),
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    // A variant can be tagged `#[weight = N]` to be chosen more or less
+    // often than the rest (e.g. a rare error case); an untagged enum falls
+    // back to a uniform pick.
+    let weights: ~[uint] = def.variants.iter().map(variant_weight).collect();
+    let uniform = weights.iter().all(|&w| w == weights[0]);
+
+    let choose = if uniform {
+        if non_recursive.is_empty() || non_recursive.len() == n {
+            quote_expr!(cx, g.gen_range(0u, $n))
+        } else {
+            quote_expr!(cx,
+                if g.size() == 0 {
+                    *g.choose($non_recursive)
+                } else {
+                    g.gen_range(0u, $n)
+                })
+        }
+    } else {
+        // This rebuilds the alias table on every `arbitrary()` call rather
+        // than caching it once per type, trading the O(1)-after-O(n) setup
+        // `Alias` advertises for simplicity; fine for the handful of
+        // variants a real enum has, so not worth the plumbing to cache it.
+        let weight_vec = expr_weight_vec(cx, span, weights);
+        if non_recursive.is_empty() || non_recursive.len() == n {
+            quote_expr!(cx, ::qc::arbitrary::Alias::new($weight_vec).sample(g))
+        } else {
+            quote_expr!(cx,
+                if g.size() == 0 {
+                    *g.choose($non_recursive)
+                } else {
+                    ::qc::arbitrary::Alias::new($weight_vec).sample(g)
+                })
+        }
+    };
+
+    quote_item!(cx,
+        impl ::qc::arbitrary::Arbitrary for $name {
+            fn arbitrary<G: ::qc::arbitrary::Gen>(g: &mut G) -> $name {
+                match $choose {
+                    $arms
+                    _ => fail!("Arbitrary for $name: variant index out of range"),
+                }
+            }
+        }
+    ).unwrap()
+}
+
+/// Expand `#[deriving(Arbitrary)]` on a struct or enum into an `Arbitrary`
+/// impl that generates each field (recursively) with `arbitrary(g)`, or, for
+/// an enum, picks a variant (uniformly, or via Vose's alias method if the
+/// variants' `#[weight = N]` weights are not all equal) before generating
+/// its fields.
+pub fn expand_deriving_arbitrary(cx: &mut ExtCtxt,
+                                  span: Span,
+                                  _mitem: @MetaItem,
+                                  item: @Item,
+                                  push: |@Item|) {
+    let name = item.ident;
+    let generated = match item.node {
+        ast::ItemStruct(def, _) => expand_struct(cx, span, name, def),
+        ast::ItemEnum(ref def, _) => expand_enum(cx, span, name, def),
+        _ => {
+            cx.span_err(span, "`deriving(Arbitrary)` only applies to structs and enums");
+            return;
+        }
+    };
+    push(generated);
+}